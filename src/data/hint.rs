@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{
+    data::{CompressionType, DataFile, LogRecord, LogRecordPos, LogRecordStatus},
+    indexer::{new_indexer, Indexer, IndexerType},
+    io::IOType,
+};
+
+const HINT_FILE_NAME: &str = "hint-index";
+
+fn hint_file_path(dir_path: &str) -> String {
+    let mut path = std::path::PathBuf::from(dir_path);
+    path.push(HINT_FILE_NAME);
+    path.to_string_lossy().into_owned()
+}
+
+// A hint file is just a regular data file whose records carry `(key, encoded LogRecordPos)`
+// instead of `(key, value)`, so it gets the same header framing and crc protection for free.
+// Each `HintFile::write` call appends one entry; nothing in this crate writes hint files during
+// merge/compaction. `load_index` below replays the entries at startup, so opening the store
+// scales with the number of entries rather than the total bytes on disk.
+pub struct HintFile {
+    data_file: DataFile,
+}
+
+impl HintFile {
+    pub fn new(dir_path: &str) -> Result<Self> {
+        let data_file = DataFile::open_at_path(
+            &hint_file_path(dir_path),
+            IOType::StandardFIO,
+            CompressionType::None,
+        )?;
+        Ok(Self { data_file })
+    }
+
+    pub fn write(&mut self, key: &[u8], pos: &LogRecordPos) -> Result<()> {
+        let record = LogRecord {
+            key: key.to_vec(),
+            val: pos.encode(),
+            status: LogRecordStatus::Normal,
+        };
+        self.data_file.write(&record)?;
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        self.data_file.io_manager.sync()
+    }
+}
+
+// Replays a hint file (if one exists) into a fresh indexer of the given type. Returns an empty
+// indexer when there is no hint file yet, so callers can fall back to a full data-file scan.
+pub fn load_index(dir_path: &str, indexer_type: IndexerType) -> Result<Box<dyn Indexer>> {
+    let mut indexer = new_indexer(indexer_type);
+
+    let path = hint_file_path(dir_path);
+    if !Path::new(&path).exists() {
+        return Ok(indexer);
+    }
+
+    let mut data_file = DataFile::open_at_path(&path, IOType::StandardFIO, CompressionType::None)?;
+    for (record, _pos, _size) in data_file.iter_records() {
+        let pos = LogRecordPos::decode(&record.val)?;
+        indexer.put(&record.key, &pos);
+    }
+
+    Ok(indexer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_hint_file_roundtrip() {
+        let dir = env::temp_dir();
+        let dir_path = dir.to_str().unwrap();
+        let _ = std::fs::remove_file(hint_file_path(dir_path));
+
+        let mut hint_file = HintFile::new(dir_path).unwrap();
+        let pos1 = LogRecordPos {
+            file_id: 1,
+            offset: 0,
+            data_size: 10,
+        };
+        let pos2 = LogRecordPos {
+            file_id: 1,
+            offset: 10,
+            data_size: 20,
+        };
+        hint_file.write(b"k1", &pos1).unwrap();
+        hint_file.write(b"k2", &pos2).unwrap();
+        hint_file.sync().unwrap();
+
+        let indexer = load_index(dir_path, IndexerType::BTree).unwrap();
+        assert_eq!(indexer.size(), 2);
+        assert_eq!(indexer.get(b"k1"), Some(pos1));
+        assert_eq!(indexer.get(b"k2"), Some(pos2));
+    }
+}
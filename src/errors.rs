@@ -7,4 +7,16 @@ pub enum Errors {
 
     #[error("invalid log record crc value")]
     InvalidLogRecordCRC,
+
+    #[error("invalid log record header")]
+    InvalidLogRecordHeader,
+
+    #[error("failed to decompress log record value")]
+    DecompressionFailed,
+
+    #[error("invalid data file format")]
+    InvalidDataFileFormat,
+
+    #[error("unsupported data file format version")]
+    UnsupportedVersion,
 }
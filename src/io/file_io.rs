@@ -1,10 +1,15 @@
-use std::{fs::OpenOptions, io::Write, os::unix::fs::FileExt};
+use std::{fs::OpenOptions, os::unix::fs::FileExt};
 
 use super::IoManager;
 use anyhow::Result;
 
 pub struct FileIoManager {
     file: std::fs::File,
+    // Tracks the append cursor ourselves instead of opening with `O_APPEND`: on Linux a
+    // `write_at`/`pwrite` against an `O_APPEND` fd ignores the explicit offset and always
+    // appends, which would make `write_at` unusable for positional rewrites on this same
+    // handle.
+    write_offset: u64,
 }
 
 impl FileIoManager {
@@ -12,10 +17,10 @@ impl FileIoManager {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
-            .append(true)
             .create(true)
             .open(file_name)?;
-        Ok(Self { file: file })
+        let write_offset = file.metadata()?.len();
+        Ok(Self { file, write_offset })
     }
 }
 
@@ -25,7 +30,13 @@ impl IoManager for FileIoManager {
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        Ok(self.file.write(buf)?)
+        let n = self.file.write_at(buf, self.write_offset)?;
+        self.write_offset += n as u64;
+        Ok(n)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        Ok(self.file.write_at(buf, offset)?)
     }
 
     fn size(&self) -> Result<u64> {
@@ -42,6 +53,7 @@ impl IoManager for FileIoManager {
 mod tests {
     use super::*;
     use std::fs::{remove_file, File};
+    use std::io::Write;
 
     #[test]
     fn test_file_io_manager_new() {
@@ -64,6 +76,23 @@ mod tests {
         remove_file(file_name).unwrap();
     }
 
+    #[test]
+    fn test_file_io_manager_write_at() {
+        let file_name = "/tmp/test_fwrite_at.txt";
+        let mut io_manager = FileIoManager::new(file_name).unwrap();
+
+        io_manager.write(b"Hello, test!").unwrap();
+
+        let bytes_written = io_manager.write_at(b"WORLD", 7).unwrap();
+        assert_eq!(bytes_written, 5);
+
+        let mut buf = vec![0u8; 12];
+        io_manager.read(&mut buf, 0).unwrap();
+        assert_eq!(&buf, b"Hello, WORLD");
+
+        remove_file(file_name).unwrap();
+    }
+
     #[test]
     fn test_file_io_manager_read() {
         let file_name = "/tmp/test_fread.txt";
@@ -1,12 +1,36 @@
-use std::fs::OpenOptions;
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
 
-use anyhow::{anyhow, Ok, Result};
-use memmap2::Mmap;
+use anyhow::Result;
+use memmap2::MmapMut;
 
 use super::IoManager;
 
+// Lower bound on how much virtual address space we reserve up front, so tiny/empty files don't
+// force a remap on the very first append.
+const MIN_RESERVE_SIZE: u64 = 1024 * 1024;
+// Reserved capacity is this multiple of the file size actually needed, so steady appends don't
+// hit the remap/ftruncate path on every single write.
+const RESERVE_GROWTH_FACTOR: u64 = 2;
+
+// Sidecar holding `data_len` as 8 little-endian bytes, rewritten on every `sync`. The file's own
+// length can't be trusted as the logical length on reopen -- it's inflated by the up-front
+// reservation, and `Drop` (which normally shrinks it back down) never runs on a crash/kill/abort.
+// The sidecar is the only record of the true length that survives that.
+const LEN_FILE_SUFFIX: &str = ".len";
+
 pub struct MMapIOManager {
-    mmap: Mmap,
+    file: File,
+    mmap: MmapMut,
+    // Logical length of the data actually written so far; bytes beyond this within `mmap` are
+    // reserved but not yet valid.
+    data_len: u64,
+    // Length the file has been truncated/mapped to, i.e. the capacity of `mmap`.
+    mapped_len: u64,
+    len_file_path: PathBuf,
 }
 
 impl MMapIOManager {
@@ -17,33 +41,132 @@ impl MMapIOManager {
             .create(true)
             .open(file_name)?;
 
-        let mmap = unsafe { Mmap::map(&file)? };
-        Ok(Self { mmap })
+        let len_file_path = Self::len_file_path(file_name);
+        let on_disk_len = file.metadata()?.len();
+        // The file is never shorter than its logical length, so a sidecar claiming more than the
+        // file holds belongs to an earlier file of the same name and is ignored.
+        let data_len = Self::read_persisted_len(&len_file_path)?
+            .filter(|&len| len <= on_disk_len)
+            .unwrap_or(on_disk_len);
+
+        let mapped_len = std::cmp::max(on_disk_len, Self::reserve_len(data_len));
+        if mapped_len != on_disk_len {
+            file.set_len(mapped_len)?;
+        }
+
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        Ok(Self {
+            file,
+            mmap,
+            data_len,
+            mapped_len,
+            len_file_path,
+        })
+    }
+
+    fn len_file_path(file_name: &str) -> PathBuf {
+        let mut path = PathBuf::from(file_name);
+        let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        path.set_file_name(format!("{}{}", file_name, LEN_FILE_SUFFIX));
+        path
+    }
+
+    fn read_persisted_len(len_file_path: &std::path::Path) -> Result<Option<u64>> {
+        match std::fs::read(len_file_path) {
+            Ok(bytes) if bytes.len() == 8 => {
+                Ok(Some(u64::from_le_bytes(bytes.try_into().unwrap())))
+            }
+            // A missing or truncated/corrupt sidecar (e.g. this file predates the sidecar, or a
+            // crash hit mid-write of the sidecar itself) just means we fall back to the on-disk
+            // length, same as before the sidecar existed.
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn persist_data_len(&self) -> Result<()> {
+        let mut len_file = File::create(&self.len_file_path)?;
+        len_file.write_all(&self.data_len.to_le_bytes())?;
+        len_file.sync_all()?;
+        Ok(())
+    }
+
+    fn reserve_len(required: u64) -> u64 {
+        std::cmp::max(MIN_RESERVE_SIZE, required * RESERVE_GROWTH_FACTOR)
+    }
+
+    // Extends the file and remaps it so that at least `required` bytes are addressable. Only
+    // called once the currently reserved space is exhausted, not on every write.
+    fn grow(&mut self, required: u64) -> Result<()> {
+        let new_len = std::cmp::max(required, Self::reserve_len(required));
+        self.file.set_len(new_len)?;
+        self.mmap = unsafe { MmapMut::map_mut(&self.file)? };
+        self.mapped_len = new_len;
+        Ok(())
+    }
+
+    // Undoes the up-front reservation so the file's on-disk length matches the data actually
+    // written. Without this, the next open would read back `file.metadata().len()` -- which is
+    // the padded/reserved length, not the logical one -- as if it were real data.
+    fn shrink_to_data_len(&mut self) -> Result<()> {
+        if self.mapped_len != self.data_len {
+            self.mmap.flush()?;
+            self.file.set_len(self.data_len)?;
+            self.mapped_len = self.data_len;
+        }
+        self.persist_data_len()
+    }
+}
+
+impl Drop for MMapIOManager {
+    fn drop(&mut self) {
+        let _ = self.shrink_to_data_len();
     }
 }
 
 impl IoManager for MMapIOManager {
-    fn read(&mut self, buf: &mut [u8], offset: u64) -> Result<usize> {
-        let end = offset as usize + buf.len();
-
-        if let Some(data) = self.mmap.get(offset as usize..end) {
-            buf.copy_from_slice(data);
-            Ok(data.len())
-        } else {
-            Err(anyhow!("Out of bounds"))
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let end = offset + buf.len() as u64;
+        if end > self.data_len {
+            return Err(anyhow::anyhow!("Out of bounds"));
+        }
+
+        buf.copy_from_slice(&self.mmap[offset as usize..end as usize]);
+        Ok(buf.len())
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let offset = self.data_len;
+        let end = offset + buf.len() as u64;
+
+        if end > self.mapped_len {
+            self.grow(end)?;
         }
+
+        self.mmap[offset as usize..end as usize].copy_from_slice(buf);
+        self.data_len = end;
+        Ok(buf.len())
     }
 
-    fn write(&mut self, _: &[u8]) -> Result<usize> {
-        unimplemented!()
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize> {
+        let end = offset + buf.len() as u64;
+        if end > self.data_len {
+            return Err(anyhow::anyhow!("Out of bounds"));
+        }
+
+        self.mmap[offset as usize..end as usize].copy_from_slice(buf);
+        Ok(buf.len())
     }
 
     fn size(&self) -> Result<u64> {
-        Ok(self.mmap.len() as u64)
+        Ok(self.data_len)
     }
 
     fn sync(&mut self) -> Result<()> {
-        unimplemented!()
+        self.mmap.flush()?;
+        self.persist_data_len()
     }
 }
 
@@ -72,7 +195,7 @@ mod tests {
         file.write_all(b"Hello, test!").unwrap();
         file.sync_all().unwrap();
 
-        let mut mio = MMapIOManager::new(&file_name).unwrap();
+        let mio = MMapIOManager::new(&file_name).unwrap();
         assert_eq!(mio.size().unwrap(), 12);
         let mut buf = vec![0u8; 5];
         let bytes_read = mio.read(&mut buf, 7).unwrap();
@@ -81,4 +204,147 @@ mod tests {
 
         remove_file(file_name).unwrap();
     }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let file_name = "/tmp/test_mmap_write.txt";
+        let _ = remove_file(file_name);
+
+        let mut mio = MMapIOManager::new(&file_name).unwrap();
+        let data = b"Hello, mmap!";
+
+        let bytes_written = mio.write(data).unwrap();
+        assert_eq!(bytes_written, data.len());
+        assert_eq!(mio.size().unwrap(), data.len() as u64);
+
+        let mut buf = vec![0u8; data.len()];
+        mio.read(&mut buf, 0).unwrap();
+        assert_eq!(&buf, data);
+
+        remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn test_write_at_patches_in_place() {
+        let file_name = "/tmp/test_mmap_write_at.txt";
+        let _ = remove_file(file_name);
+
+        let mut mio = MMapIOManager::new(&file_name).unwrap();
+        mio.write(b"Hello, mmap!").unwrap();
+
+        let bytes_written = mio.write_at(b"WORLD", 7).unwrap();
+        assert_eq!(bytes_written, 5);
+
+        let mut buf = vec![0u8; 12];
+        mio.read(&mut buf, 0).unwrap();
+        assert_eq!(&buf, b"Hello, WORLD");
+
+        assert!(mio.write_at(b"oops", 9).is_err());
+
+        remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn test_write_grows_past_initial_reservation() {
+        let file_name = "/tmp/test_mmap_grow.txt";
+        let _ = remove_file(file_name);
+
+        let mut mio = MMapIOManager::new(&file_name).unwrap();
+        let chunk = vec![b'x'; 1024];
+
+        let mut total = 0u64;
+        while total < MIN_RESERVE_SIZE + 1 {
+            mio.write(&chunk).unwrap();
+            total += chunk.len() as u64;
+        }
+
+        assert_eq!(mio.size().unwrap(), total);
+
+        let mut buf = vec![0u8; chunk.len()];
+        mio.read(&mut buf, 0).unwrap();
+        assert_eq!(buf, chunk);
+
+        remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn test_reopen_after_drop_reports_true_data_len() {
+        let file_name = "/tmp/test_mmap_reopen.txt";
+        let _ = remove_file(file_name);
+
+        {
+            let mut mio = MMapIOManager::new(&file_name).unwrap();
+            mio.write(b"Hello").unwrap();
+            mio.sync().unwrap();
+            // `mio` is dropped at the end of this block, which must shrink the file back down
+            // to its logical length before the next `MMapIOManager::new` sees it.
+        }
+
+        let on_disk_len = std::fs::metadata(file_name).unwrap().len();
+        assert_eq!(on_disk_len, 5);
+
+        let mut mio = MMapIOManager::new(&file_name).unwrap();
+        assert_eq!(mio.size().unwrap(), 5);
+
+        let mut buf = vec![0u8; 5];
+        mio.read(&mut buf, 0).unwrap();
+        assert_eq!(&buf, b"Hello");
+
+        // Further appends should land right after the recovered data, not near the old
+        // reserved-but-discarded capacity.
+        mio.write(b", world").unwrap();
+        assert_eq!(mio.size().unwrap(), 12);
+
+        let mut buf = vec![0u8; 12];
+        mio.read(&mut buf, 0).unwrap();
+        assert_eq!(&buf, b"Hello, world");
+
+        remove_file(file_name).unwrap();
+        let _ = remove_file(MMapIOManager::len_file_path(file_name));
+    }
+
+    #[test]
+    fn test_reopen_after_crash_without_drop_reports_true_data_len() {
+        let file_name = "/tmp/test_mmap_reopen_no_drop.txt";
+        let _ = remove_file(file_name);
+        let _ = remove_file(MMapIOManager::len_file_path(file_name));
+
+        let mut mio = MMapIOManager::new(&file_name).unwrap();
+        mio.write(b"Hello").unwrap();
+        mio.sync().unwrap();
+        // Simulate a crash/kill right after `sync` returns: `Drop` never runs, so the file is
+        // still sitting at its full padded/reserved length on disk.
+        std::mem::forget(mio);
+
+        let on_disk_len = std::fs::metadata(file_name).unwrap().len();
+        assert!(on_disk_len > 5, "expected the padded reservation to still be on disk");
+
+        let mut mio = MMapIOManager::new(&file_name).unwrap();
+        assert_eq!(mio.size().unwrap(), 5);
+
+        let mut buf = vec![0u8; 5];
+        mio.read(&mut buf, 0).unwrap();
+        assert_eq!(&buf, b"Hello");
+
+        mio.write(b", world").unwrap();
+        assert_eq!(mio.size().unwrap(), 12);
+        mio.sync().unwrap();
+
+        remove_file(file_name).unwrap();
+        remove_file(MMapIOManager::len_file_path(file_name)).unwrap();
+    }
+
+    #[test]
+    fn test_sync() {
+        let file_name = "/tmp/test_mmap_sync.txt";
+        let _ = remove_file(file_name);
+
+        let mut mio = MMapIOManager::new(&file_name).unwrap();
+        mio.write(b"data").unwrap();
+
+        assert!(mio.sync().is_ok());
+
+        remove_file(file_name).unwrap();
+        remove_file(MMapIOManager::len_file_path(file_name)).unwrap();
+    }
 }
@@ -8,6 +8,10 @@ pub mod mmap;
 pub trait IoManager {
     fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize>;
     fn write(&mut self, buf: &[u8]) -> Result<usize>;
+    // Writes `buf` at an explicit offset rather than the append cursor, for out-of-place
+    // rewrites (patching a file header, checkpointing an index/hint file, compaction touching
+    // up tombstones) that need deterministic placement.
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize>;
     fn size(&self) -> Result<u64>;
     fn sync(&mut self) -> Result<()>;
 }